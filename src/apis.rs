@@ -4,11 +4,12 @@ use reqwest::Method;
 use std::fs::File;
 use std::io::prelude::*;
 
-use async_openai::types::{
-    ChatCompletionRequestMessageArgs, CreateChatCompletionRequestArgs, Role,
-};
+use async_openai::types::CreateEmbeddingRequestArgs;
 use async_openai::Client as OpenAiClient;
 
+use crate::llm::{ChatBackend, CohereBackend, OpenAiBackend};
+use crate::provider::LlmProvider;
+
 #[derive(Clone)]
 pub enum ArrService {
     Sonarr,
@@ -31,7 +32,7 @@ pub enum HttpMethod {
     Delete,
 }
 
-fn get_credentials() -> toml::Value {
+pub(crate) fn get_credentials() -> toml::Value {
     // Read credentials.toml file to get keys
     let mut file = File::open("credentials.toml").expect("Failed to open credentials file");
     let mut contents = String::new();
@@ -54,68 +55,111 @@ pub fn get_discord_token() -> String {
     discord_token
 }
 
+/// Get the plain openai_api_key from credentials.toml
+pub(crate) fn get_openai_api_key() -> String {
+    get_credentials()["openai_api_key"]
+        .as_str()
+        .expect("Expected a openai_api_key in the credentials.toml file")
+        .to_string()
+}
+
 /// Get openai client
 pub fn get_openai() -> OpenAiClient {
+    OpenAiClient::new().with_api_key(get_openai_api_key())
+}
+
+/// Build the chat backend selected by the `backend` key in credentials.toml, defaulting to
+/// OpenAI's `gpt-4-0613` when unset so existing setups keep working unchanged.
+pub fn get_chat_backend() -> Box<dyn ChatBackend> {
     let cred = get_credentials();
 
-    // Configure the client with your openai api key
-    let openai_api_key = cred["openai_api_key"]
-        .as_str()
-        .expect("Expected a openai_api_key in the credentials.toml file")
-        .to_string();
-    OpenAiClient::new().with_api_key(openai_api_key)
+    match cred.get("backend").and_then(toml::Value::as_str) {
+        Some("cohere") => {
+            let cohere = cred["cohere"]
+                .as_table()
+                .expect("Expected a [cohere] section in credentials.toml");
+            let api_key = cohere["api_key"]
+                .as_str()
+                .expect("Expected an api_key in the [cohere] section")
+                .to_string();
+            let model = cohere
+                .get("model")
+                .and_then(toml::Value::as_str)
+                .unwrap_or("command-r")
+                .to_string();
+            Box::new(CohereBackend::new(api_key, model))
+        }
+        _ => Box::new(OpenAiBackend::new(get_openai(), "gpt-4-0613")),
+    }
 }
 
-/// Use gpt to query information
-pub async fn gpt_info_query(model: String, data: String, prompt: String) -> Result<String, String> {
+/// Embed `text` with OpenAI's embeddings API, for semantic caches like `media_lookup`'s.
+pub async fn get_embedding(text: &str) -> anyhow::Result<Vec<f32>> {
     let openai = get_openai();
 
-    // Search with gpt through the memories to answer the query
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(model)
-        .messages([
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::System)
-                .content(data)
-                .build()
-                .unwrap(),
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::User)
-                .content(prompt)
-                .build()
-                .unwrap(),
-        ])
-        .build()
-        .unwrap();
+    let request = CreateEmbeddingRequestArgs::default()
+        .model("text-embedding-ada-002")
+        .input([text])
+        .build()?;
+
+    let embedding = openai
+        .embeddings()
+        .create(request)
+        .await?
+        .data
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("OpenAI returned no embedding"))?
+        .embedding;
+    Ok(embedding)
+}
+
+/// Use gpt to query information
+#[tracing::instrument(skip(data, prompt), fields(model = %model, tries, latency_ms))]
+pub async fn gpt_info_query(model: String, data: String, prompt: String) -> Result<String, String> {
+    let provider = crate::provider::HttpLlmProvider::from_credentials().with_model(model);
+    let messages = vec![
+        crate::provider::ChatMessage {
+            role: "system",
+            content: data,
+        },
+        crate::provider::ChatMessage {
+            role: "user",
+            content: prompt,
+        },
+    ];
 
     // Retry the request if it fails
+    let started_at = std::time::Instant::now();
     let mut tries = 0;
     let response = loop {
-        let response = openai.chat().create(request.clone()).await;
-        if let Ok(response) = response {
-            break Ok(response);
+        let response = provider
+            .complete(messages.clone(), crate::provider::CompletionOpts::default())
+            .await;
+        if response.is_ok() {
+            break response;
         }
         tries += 1;
         if tries >= 3 {
             break response;
         }
     };
-    // Return from errors
-    if response.is_err() {
-        return Err("Failed to get response from openai".to_string());
-    }
-    let result = response
-        .unwrap()
-        .choices
-        .first()
-        .unwrap()
-        .message
-        .content
-        .clone();
-    Ok(result)
+    let span = tracing::Span::current();
+    span.record("tries", tries);
+    span.record("latency_ms", started_at.elapsed().as_millis());
+
+    response
+        .map_err(|error| {
+            tracing::error!(%error, "llm provider call failed");
+            "Failed to get response from llm provider".to_string()
+        })?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Llm provider returned no choices".to_string())
 }
 
 /// Make a request to an arr service
+#[tracing::instrument(skip(data), fields(service = %service, method = ?method, url = %url, status))]
 pub async fn arr_request(
     method: HttpMethod,
     service: ArrService,
@@ -165,13 +209,9 @@ pub async fn arr_request(
         request
     };
 
-    let response = request
-        .send()
-        .await
-        .expect("Failed to send request")
-        .text()
-        .await
-        .expect("Failed to get response");
+    let response = request.send().await.expect("Failed to send request");
+    tracing::Span::current().record("status", response.status().as_u16());
+    let response = response.text().await.expect("Failed to get response");
 
     serde_json::from_str(&response).expect("Failed to parse json")
 }
@@ -0,0 +1,77 @@
+//! Embeddings-backed semantic cache for `media_lookup`, so the bot doesn't re-run an expensive
+//! Sonarr/Radarr/TMDB search every turn just because the system prompt insists on always
+//! looking up ids.
+//!
+//! Similarity threshold and TTL are configurable via the `[media_lookup_cache]` section of
+//! credentials.toml, falling back to sane defaults when absent.
+
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+struct CacheEntry {
+    query_embedding: Vec<f32>,
+    result: String,
+    inserted_at: Instant,
+}
+
+static MEDIA_LOOKUP_CACHE: Lazy<Mutex<Vec<CacheEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+fn config() -> (f32, Duration) {
+    let cred = crate::apis::get_credentials();
+    let section = cred
+        .get("media_lookup_cache")
+        .and_then(toml::Value::as_table);
+    let similarity_threshold = section
+        .and_then(|section| section.get("similarity_threshold"))
+        .and_then(toml::Value::as_float)
+        .unwrap_or(0.92) as f32;
+    let ttl_seconds = section
+        .and_then(|section| section.get("ttl_seconds"))
+        .and_then(toml::Value::as_integer)
+        .unwrap_or(300);
+    (
+        similarity_threshold,
+        Duration::from_secs(ttl_seconds as u64),
+    )
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Return the cached result for `query` if a cache entry within the similarity threshold and
+/// TTL exists, otherwise run `compute` and cache its result.
+pub async fn media_lookup_cached<F>(query: &str, compute: F) -> anyhow::Result<String>
+where
+    F: Future<Output = anyhow::Result<String>>,
+{
+    let (similarity_threshold, ttl) = config();
+    let query_embedding = crate::apis::get_embedding(query).await?;
+
+    {
+        let mut cache = MEDIA_LOOKUP_CACHE.lock().await;
+        cache.retain(|entry| entry.inserted_at.elapsed() < ttl);
+        if let Some(entry) = cache.iter().find(|entry| {
+            cosine_similarity(&entry.query_embedding, &query_embedding) >= similarity_threshold
+        }) {
+            return Ok(entry.result.clone());
+        }
+    }
+
+    let result = compute.await?;
+    MEDIA_LOOKUP_CACHE.lock().await.push(CacheEntry {
+        query_embedding,
+        result: result.clone(),
+        inserted_at: Instant::now(),
+    });
+    Ok(result)
+}
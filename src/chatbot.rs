@@ -1,23 +1,24 @@
+//! The tool catalogue the function-calling engine advertises to the model, plus the plumbing to
+//! run one round of it: `get_functions()`/`get_chat_completions()` describe each tool,
+//! `run_function` dispatches a call the model asked for, and `complete_with_retry` wraps a
+//! [`crate::llm::ChatBackend`] round with retry/backoff. `main::run_chat_completion` drives the
+//! actual multi-round loop for natural-language chat; `commands.rs` calls `run_function` directly
+//! to bypass the LLM for slash commands.
+
+use crate::llm::{CompletionResult, StreamDelta};
 use crate::plugins;
 use anyhow::anyhow;
 use async_openai::types::{
     ChatCompletionFunctions, ChatCompletionFunctionsArgs, ChatCompletionRequestMessage,
-    ChatCompletionRequestMessageArgs, CreateChatCompletionRequestArgs, Role,
 };
-use chrono::Local;
-use futures::Future;
 use serde_json::json;
-use serenity::{model::channel::Message as DiscordMessage, prelude::Context as DiscordContext};
-use std::{collections::HashMap, pin::Pin};
-
-const USER_EMOJI: &str = "💬 ";
-const BOT_EMOJI: &str = "☑️ ";
+use std::{collections::HashMap, future::Future, pin::Pin, time::Duration};
 
 #[derive(Debug)]
-struct Func {
-    name: String,
-    description: String,
-    parameters: Vec<Param>,
+pub(crate) struct Func {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) parameters: Vec<Param>,
     call_func: FuncType,
 }
 
@@ -36,11 +37,11 @@ type FuncType =
     fn(HashMap<String, String>) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>>;
 
 #[derive(Debug, Clone)]
-struct Param {
-    name: String,
-    description: String,
-    required: bool,
-    enum_values: Option<Vec<String>>,
+pub(crate) struct Param {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) required: bool,
+    pub(crate) enum_values: Option<Vec<String>>,
 }
 impl Param {
     fn new(name: &str, description: &str) -> Self {
@@ -94,7 +95,8 @@ fn func_to_chat_completion(func: &Func) -> ChatCompletionFunctions {
         .unwrap()
 }
 
-fn get_chat_completions() -> Vec<ChatCompletionFunctions> {
+/// The tool schema to offer the model, in whatever shape [`crate::llm::ChatBackend`] needs.
+pub(crate) fn get_chat_completions() -> Vec<ChatCompletionFunctions> {
     get_functions()
         .iter()
         .map(func_to_chat_completion)
@@ -102,7 +104,7 @@ fn get_chat_completions() -> Vec<ChatCompletionFunctions> {
 }
 
 /// Get available functions data
-fn get_functions() -> Vec<Func> {
+pub(crate) fn get_functions() -> Vec<Func> {
     // Common parameters for the functions
     let format_param = Param::new("format", "The format of the media to be searched for")
         .with_enum_values(&["movie", "series"]);
@@ -185,7 +187,7 @@ fn get_functions() -> Vec<Func> {
 }
 
 /// Run function
-async fn run_function(
+pub(crate) async fn run_function(
     name: String,
     args: serde_json::Value,
     user_name: &str,
@@ -196,9 +198,29 @@ async fn run_function(
         if func.name == name {
             let mut args_map = HashMap::new();
             args_map.insert("user_name".to_string(), user_name.to_string());
-            for (key, value) in args.as_object().unwrap() {
-                args_map.insert(key.clone(), value.as_str().unwrap().to_string());
+            let args_object = args
+                .as_object()
+                .ok_or_else(|| anyhow!("Function arguments for {name} were not a JSON object"))?;
+            for (key, value) in args_object {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Argument {key} for {name} was not a string"))?;
+                args_map.insert(key.clone(), value.to_string());
             }
+
+            // media_lookup is run on nearly every turn since the system prompt insists on
+            // always re-checking ids, so cache it by embedding similarity rather than hitting
+            // Sonarr/Radarr/TMDB again for a near-identical query
+            if func.name == "media_lookup" {
+                let cache_query = format!(
+                    "{}|{}",
+                    args_map.get("searches").map_or("", String::as_str),
+                    args_map.get("query").map_or("", String::as_str)
+                );
+                return crate::cache::media_lookup_cached(&cache_query, (func.call_func)(args_map))
+                    .await;
+            }
+
             return (func.call_func)(args_map).await;
         }
     }
@@ -206,184 +228,36 @@ async fn run_function(
     Err(anyhow!("Function not found"))
 }
 
-/// Run the chat completition
-pub async fn run_chat_completition(
-    ctx: DiscordContext,
-    mut bot_message: DiscordMessage,
-    message_history_text: String,
-    user_name: String,
-    chat_query: Vec<ChatCompletionRequestMessage>,
-) {
-    // The initial messages to send to the API
-    let mut chat_query: Vec<ChatCompletionRequestMessage> = chat_query;
-
-    // Rerun the chat completition until either no function calls left, or max iterations reached
-    let mut extra_history_text: String = String::new();
-    let mut final_response: String = String::new();
-    let mut counter = 0;
-    while counter < 10 {
-        let request = CreateChatCompletionRequestArgs::default()
-            .max_tokens(512u16)
-            .model("gpt-4-0613")
-            .messages(chat_query.clone())
-            .functions(get_chat_completions())
-            .function_call("auto")
-            .build()
-            .unwrap();
-
-        let response_message = async_openai::Client::new()
-            .chat()
-            .create(request)
-            .await
-            .unwrap()
-            .choices
-            .get(0)
-            .unwrap()
-            .message
-            .clone();
-
-        if let Some(function_call) = response_message.function_call {
-            let function_name = function_call.name;
-            let function_args: serde_json::Value = function_call.arguments.parse().unwrap();
-
-            // Edit the discord message with function call in progress
-            let ctx_c = ctx.clone();
-            let mut bot_message_c = bot_message.clone();
-            let new_message = format!(
-                "{message_history_text}{extra_history_text}⌛ Running function {function_name} with arguments {function_args}"
-            );
-            tokio::spawn(async move {
-                bot_message_c
-                    .edit(&ctx_c.http, |msg| msg.content(new_message))
-                    .await
-                    .unwrap();
-            });
-
-            let function_response =
-                run_function(function_name.clone(), function_args, &user_name).await;
-            // Get function response as string if either ok or error
-            let function_response_message =
-                function_response.map_or_else(|error| error.to_string(), |response| response);
-            // Truncate the function response to 100 characters
-            let function_response_short = if function_response_message.len() > 150 {
-                let trimmed_message = function_response_message
-                    .chars()
-                    .take(150)
-                    .collect::<String>();
-                format!("{trimmed_message}...")
-            } else {
-                function_response_message.clone()
-            };
+/// Maximum number of retries for a completion request that fails with a transient (429/5xx)
+/// error, with exponential backoff between attempts.
+const MAX_COMPLETION_RETRIES: u32 = 3;
 
-            // Edit the discord message with function call results
-            extra_history_text.push_str(
-                format!("🎬 Ran function {function_name} {function_response_short}\n",).as_str(),
-            );
-            let ctx_c = ctx.clone();
-            let mut bot_message_c = bot_message.clone();
-            let new_message = format!("{message_history_text}{extra_history_text}");
-            tokio::spawn(async move {
-                bot_message_c
-                    .edit(&ctx_c.http, |msg| msg.content(new_message))
-                    .await
-                    .unwrap();
-            });
-
-            chat_query.push(
-                ChatCompletionRequestMessageArgs::default()
-                    .role(Role::Function)
-                    .name(function_name)
-                    .content(function_response_message)
-                    .build()
-                    .unwrap(),
-            );
-            counter += 1;
-        } else {
-            final_response = response_message.content.unwrap();
-            break;
-        }
-    }
-
-    // Edit the discord message finalised
-    bot_message
-        .edit(&ctx.http, |msg| {
-            msg.content(format!(
-                "{message_history_text}{extra_history_text}✅ {final_response}"
-            ))
-        })
-        .await
-        .unwrap();
+/// Whether `error` looks like a transient rate-limit or server error worth retrying.
+fn is_retryable(error: &anyhow::Error) -> bool {
+    let message = error.to_string();
+    ["429", "500", "502", "503", "504"]
+        .iter()
+        .any(|code| message.contains(code))
 }
 
-/// Process the chat message from the user
-pub async fn process_chat(
-    user_name: String,            // The users name
-    users_text: String,           // Users text to bot
-    ctx: DiscordContext,          // The discord context
-    bot_message: DiscordMessage,  // The message reply to the user
-    message_history_text: String, // The message history text, each starts with emoji identifying role
-) {
-    // Go through each line of message_history_text, if it starts with 💬 add it to user_text_total
-    let mut user_text_total = String::new();
-    for line in message_history_text.lines() {
-        if line.starts_with(USER_EMOJI) {
-            user_text_total.push_str(line.replace(USER_EMOJI, "").as_str());
+/// Run one completion round on `backend`, retrying transient (429/5xx) failures with backoff.
+pub(crate) async fn complete_with_retry(
+    backend: &dyn crate::llm::ChatBackend,
+    chat_query: &[ChatCompletionRequestMessage],
+    on_delta: &mut (dyn FnMut(StreamDelta) + Send),
+) -> anyhow::Result<CompletionResult> {
+    let mut attempt = 0;
+    loop {
+        match backend
+            .complete_stream(chat_query.to_vec(), get_chat_completions(), on_delta)
+            .await
+        {
+            Ok(result) => return Ok(result),
+            Err(error) if attempt < MAX_COMPLETION_RETRIES && is_retryable(&error) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempt))).await;
+            }
+            Err(error) => return Err(error),
         }
     }
-    // Add the users latest message
-    user_text_total.push_str(&users_text);
-
-    // Get current date and time in DD/MM/YYYY and HH:MM:SS format
-    let date = Local::now().format("%d/%m/%Y").to_string();
-    let time = Local::now().format("%H:%M").to_string();
-
-    let mut chat_query: Vec<ChatCompletionRequestMessage> = vec![
-        ChatCompletionRequestMessageArgs::default()
-            .role(Role::System)
-            .content(format!("You are media management assistant called CineMatic, enthusiastic, knowledgeable and passionate about all things media\nYou always run lookups to ensure correct id, do not rely on chat history, if the data you have received does not contain what you need you reply with the truthful answer of unknown, responses should all be on one line (with comma separation) and compact language, use emojis to express emotion to the user. The current date is {date}, the current time is {time}"))
-            .build()
-            .unwrap(),
-    ];
-    // Add message history minus the most recent line
-    let mut just_history = if message_history_text.is_empty() {
-        String::new()
-    } else {
-        message_history_text[..message_history_text.len() - 1].to_string()
-    };
-    // If it contains a \n then it has history
-    if just_history.contains('\n') {
-        // Remove the last line
-        just_history =
-            just_history[..just_history.rfind('\n').unwrap_or(just_history.len())].to_string();
-        chat_query.push(
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::System)
-                .content(format!(
-                    "Message history:\n{}",
-                    just_history
-                        .replace(USER_EMOJI, "User: ")
-                        .replace(BOT_EMOJI, "CineMatic: ")
-                ))
-                .build()
-                .unwrap(),
-        );
-    }
-    // Add users message
-    chat_query.push(
-        ChatCompletionRequestMessageArgs::default()
-            .role(Role::User)
-            .content(users_text.clone())
-            .build()
-            .unwrap(),
-    );
-
-    // Run chat completion
-    run_chat_completition(
-        ctx,
-        bot_message,
-        message_history_text,
-        user_name,
-        chat_query,
-    )
-    .await;
 }
@@ -0,0 +1,80 @@
+//! Registers the functions from `chatbot::get_functions()` as native Discord slash commands.
+//!
+//! Every interaction used to go through free-text chat and a full GPT round, even for
+//! deterministic operations. `Func`/`Param` already carry names, descriptions, `required` and
+//! `enum_values`, so we build application-command definitions directly from them and route the
+//! supplied options straight into `run_function`, bypassing the LLM entirely.
+
+use crate::chatbot::{get_functions, run_function};
+use serenity::{
+    builder::CreateApplicationCommands,
+    model::application::{
+        command::CommandOptionType,
+        interaction::{
+            application_command::ApplicationCommandInteraction, InteractionResponseType,
+        },
+    },
+    prelude::Context as DiscordContext,
+};
+
+/// Build the global application-command definitions from `get_functions()`.
+pub fn build_commands(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    for func in get_functions() {
+        commands.create_application_command(|command| {
+            command.name(&func.name).description(&func.description);
+            for param in &func.parameters {
+                command.create_option(|option| {
+                    option
+                        .name(&param.name)
+                        .description(&param.description)
+                        .kind(CommandOptionType::String)
+                        .required(param.required);
+                    if let Some(enum_values) = &param.enum_values {
+                        for value in enum_values {
+                            option.add_string_choice(value, value);
+                        }
+                    }
+                    option
+                });
+            }
+            command
+        });
+    }
+    commands
+}
+
+/// Run the function a slash command invoked and reply with its result, skipping `process_chat`
+/// and the LLM round-trip entirely.
+pub async fn handle_command(
+    ctx: &DiscordContext,
+    interaction: &ApplicationCommandInteraction,
+) -> anyhow::Result<()> {
+    let user_name = interaction.user.name.clone();
+
+    let args = serde_json::Value::Object(
+        interaction
+            .data
+            .options
+            .iter()
+            .filter_map(|option| {
+                let value = option.value.as_ref()?.as_str()?;
+                Some((
+                    option.name.clone(),
+                    serde_json::Value::String(value.to_owned()),
+                ))
+            })
+            .collect(),
+    );
+
+    let result = run_function(interaction.data.name.clone(), args, &user_name).await;
+    let content = result.unwrap_or_else(|error| error.to_string());
+
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| message.content(content))
+        })
+        .await?;
+    Ok(())
+}
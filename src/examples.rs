@@ -0,0 +1,131 @@
+//! Embeddings-backed few-shot example retrieval, implementing the `Examples.get_examples` step.
+//!
+//! A corpus of `(query, action)` pairs lives in `examples.toml`. Each example's query is
+//! embedded once and cached in sled keyed by a hash of the query text, so a restart doesn't
+//! re-pay for every embedding call. At request time the live query is embedded and compared to
+//! every cached vector by cosine similarity; the closest few examples come back as alternating
+//! user/assistant messages ready to inject before the live user turn.
+
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestMessageArgs, Role};
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Dimensionality of `text-embedding-ada-002`'s output. A cached vector with a different length
+/// means the embedding model has changed since it was cached, so it's discarded and recomputed.
+const EMBEDDING_DIM: usize = 1536;
+/// How many of the closest examples to inject as few-shot messages.
+const TOP_K: usize = 3;
+
+/// Opened once and reused across calls, like `history::HistoryStore`, instead of reopening the
+/// sled db on every turn.
+static EMBEDDINGS_DB: Lazy<sled::Db> =
+    Lazy::new(|| sled::open("examples_embeddings.sled").expect("Failed to open examples db"));
+
+struct Example {
+    query: String,
+    action: String,
+}
+
+fn load_examples() -> anyhow::Result<Vec<Example>> {
+    let contents = std::fs::read_to_string("examples.toml")?;
+    let parsed: toml::Value = contents.parse()?;
+    let examples = parsed["example"].as_array().ok_or_else(|| {
+        anyhow::anyhow!("Expected an array of [[example]] entries in examples.toml")
+    })?;
+
+    examples
+        .iter()
+        .map(|example| {
+            let query = example["query"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Example missing a query"))?
+                .to_string();
+            let action = example["action"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Example missing an action"))?
+                .to_string();
+            Ok(Example { query, action })
+        })
+        .collect()
+}
+
+fn hash_key(text: &str) -> [u8; 8] {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish().to_be_bytes()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Embed `query`, reusing the cached vector in `db` unless it's missing or the wrong
+/// dimensionality for the current embedding model.
+async fn embed_cached(db: &sled::Db, query: &str) -> anyhow::Result<Vec<f32>> {
+    let key = hash_key(query);
+    if let Some(bytes) = db.get(key)? {
+        let cached: Vec<f32> = serde_json::from_slice(&bytes)?;
+        if cached.len() == EMBEDDING_DIM {
+            return Ok(cached);
+        }
+    }
+
+    let embedding = crate::apis::get_embedding(query).await?;
+    db.insert(key, serde_json::to_vec(&embedding)?)?;
+    Ok(embedding)
+}
+
+/// Retrieve the examples most similar to `query_text`, as alternating user/assistant messages.
+/// Falls back to no examples (rather than failing the whole request) if the corpus is missing
+/// or an embedding call fails, since a retrieval hiccup shouldn't block the user's actual turn.
+pub async fn get_examples(query_text: &str) -> Vec<ChatCompletionRequestMessage> {
+    match get_examples_inner(query_text).await {
+        Ok(messages) => messages,
+        Err(error) => {
+            tracing::warn!(%error, "Error retrieving few-shot examples");
+            Vec::new()
+        }
+    }
+}
+
+async fn get_examples_inner(query_text: &str) -> anyhow::Result<Vec<ChatCompletionRequestMessage>> {
+    let examples = load_examples()?;
+    if examples.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let query_embedding = crate::apis::get_embedding(query_text).await?;
+
+    let mut scored = Vec::with_capacity(examples.len());
+    for example in examples {
+        let embedding = embed_cached(&EMBEDDINGS_DB, &example.query).await?;
+        let score = cosine_similarity(&query_embedding, &embedding);
+        scored.push((score, example));
+    }
+    scored.sort_by(|(score_a, _), (score_b, _)| score_b.total_cmp(score_a));
+
+    let mut messages = Vec::new();
+    for (_, example) in scored.into_iter().take(TOP_K) {
+        messages.push(
+            ChatCompletionRequestMessageArgs::default()
+                .role(Role::User)
+                .content(example.query)
+                .build()?,
+        );
+        messages.push(
+            ChatCompletionRequestMessageArgs::default()
+                .role(Role::Assistant)
+                .content(example.action)
+                .build()?,
+        );
+    }
+    Ok(messages)
+}
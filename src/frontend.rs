@@ -0,0 +1,177 @@
+//! Abstracts the platform operations `process_chat`/`message` need so the chat pipeline in
+//! `main` isn't hard-wired to serenity: receiving an incoming message, posting the initial
+//! reply, and editing it (and any overflow continuations) as the answer streams in.
+
+use async_trait::async_trait;
+use serenity::{model::channel::Message as DiscordMessage, prelude::Context as DiscordContext};
+
+/// An incoming message as seen by `process_chat`, independent of the platform it arrived on.
+pub struct IncomingMessage {
+    pub author_id: String,
+    pub author_name: String,
+    pub content: String,
+    /// Platform-native id of the message being replied to, if this message is a reply.
+    pub reply_to: Option<String>,
+}
+
+/// The handful of platform operations the legacy completion pipeline needs. A message id
+/// returned by `reply`/`send` is an opaque string so the same [`crate::history::HistoryStore`]
+/// can key on it regardless of platform (Discord snowflakes vs. Matrix event ids).
+#[async_trait]
+pub trait ChatFrontend: Send + Sync {
+    /// Post `text` as a reply to the incoming message that started this turn.
+    async fn reply(&self, text: &str) -> anyhow::Result<String>;
+    /// Post `text` as a new message continuing the current reply, without itself being a
+    /// reply-to (used for overflow continuations of a long answer).
+    async fn send(&self, text: &str) -> anyhow::Result<String>;
+    /// Replace the content of a previously `reply`/`send`-produced message.
+    async fn edit(&self, message_id: &str, text: &str) -> anyhow::Result<()>;
+}
+
+/// Discord implementation, wrapping the serenity calls the bot used to make directly.
+#[derive(Clone)]
+pub struct DiscordFrontend {
+    ctx: DiscordContext,
+    incoming: DiscordMessage,
+}
+
+impl DiscordFrontend {
+    pub fn new(ctx: DiscordContext, incoming: DiscordMessage) -> Self {
+        Self { ctx, incoming }
+    }
+}
+
+#[async_trait]
+impl ChatFrontend for DiscordFrontend {
+    async fn reply(&self, text: &str) -> anyhow::Result<String> {
+        let sent = self.incoming.reply(&self.ctx.http, text).await?;
+        Ok(sent.id.0.to_string())
+    }
+
+    async fn send(&self, text: &str) -> anyhow::Result<String> {
+        let sent = self.incoming.channel_id.say(&self.ctx.http, text).await?;
+        Ok(sent.id.0.to_string())
+    }
+
+    async fn edit(&self, message_id: &str, text: &str) -> anyhow::Result<()> {
+        let message_id: u64 = message_id.parse()?;
+        self.ctx
+            .http
+            .edit_message(
+                self.incoming.channel_id.0,
+                message_id,
+                &serde_json::json!({ "content": text }),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Matrix implementation, built on matrix-rust-sdk, so the same media-assistant pipeline runs
+/// against a Matrix homeserver instead of Discord.
+#[derive(Clone)]
+pub struct MatrixFrontend {
+    room: matrix_sdk::room::Joined,
+    reply_to: matrix_sdk::ruma::OwnedEventId,
+}
+
+impl MatrixFrontend {
+    pub fn new(room: matrix_sdk::room::Joined, reply_to: matrix_sdk::ruma::OwnedEventId) -> Self {
+        Self { room, reply_to }
+    }
+}
+
+#[async_trait]
+impl ChatFrontend for MatrixFrontend {
+    async fn reply(&self, text: &str) -> anyhow::Result<String> {
+        use matrix_sdk::ruma::events::room::message::RoomMessageEventContent;
+
+        let content = RoomMessageEventContent::text_plain(text).make_reply_to_raw(&self.reply_to);
+        let response = self.room.send(content, None).await?;
+        Ok(response.event_id.to_string())
+    }
+
+    async fn send(&self, text: &str) -> anyhow::Result<String> {
+        use matrix_sdk::ruma::events::room::message::RoomMessageEventContent;
+
+        let content = RoomMessageEventContent::text_plain(text);
+        let response = self.room.send(content, None).await?;
+        Ok(response.event_id.to_string())
+    }
+
+    async fn edit(&self, message_id: &str, text: &str) -> anyhow::Result<()> {
+        use matrix_sdk::ruma::{events::room::message::RoomMessageEventContent, OwnedEventId};
+
+        let event_id = OwnedEventId::try_from(message_id)?;
+        let new_content = RoomMessageEventContent::text_plain(text);
+        let replacement = new_content.make_replacement(event_id);
+        self.room.send(replacement, None).await?;
+        Ok(())
+    }
+}
+
+/// Log in to the homeserver configured in the `[matrix]` section of credentials.toml, then sync
+/// forever, dispatching every text message in a joined room through the same relevance-check and
+/// completion pipeline the Discord frontend uses.
+pub async fn run_matrix(history_db: crate::history::HistoryStore) -> anyhow::Result<()> {
+    use matrix_sdk::{
+        config::SyncSettings,
+        room::Room,
+        ruma::events::room::message::{MessageType, OriginalSyncRoomMessageEvent},
+        Client,
+    };
+
+    let cred = crate::apis::get_credentials();
+    let matrix = cred["matrix"]
+        .as_table()
+        .ok_or_else(|| anyhow::anyhow!("Expected a [matrix] section in credentials.toml"))?;
+    let homeserver = matrix["homeserver"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Expected a homeserver in the [matrix] section"))?;
+    let username = matrix["username"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Expected a username in the [matrix] section"))?;
+    let password = matrix["password"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Expected a password in the [matrix] section"))?;
+
+    let client = Client::builder().homeserver_url(homeserver).build().await?;
+    client
+        .login_username(username, password)
+        .initial_device_display_name("CineMatic")
+        .send()
+        .await?;
+
+    client.add_event_handler({
+        let history_db = history_db.clone();
+        move |event: OriginalSyncRoomMessageEvent, room: Room, client: Client| {
+            let history_db = history_db.clone();
+            async move {
+                let Room::Joined(room) = room else {
+                    return;
+                };
+                // Don't reply to our own messages
+                if event.sender == client.user_id().expect("Logged-in client has a user id") {
+                    return;
+                }
+                let MessageType::Text(content) = event.content.msgtype else {
+                    return;
+                };
+
+                let incoming = crate::frontend::IncomingMessage {
+                    author_id: event.sender.to_string(),
+                    author_name: event.sender.localpart().to_string(),
+                    content: content.body,
+                    reply_to: None,
+                };
+                let frontend: std::sync::Arc<dyn ChatFrontend> =
+                    std::sync::Arc::new(MatrixFrontend::new(room, event.event_id.clone()));
+
+                crate::handle_incoming(history_db, frontend, incoming).await;
+            }
+        }
+    });
+
+    client.sync(SyncSettings::default()).await?;
+    Ok(())
+}
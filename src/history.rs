@@ -0,0 +1,49 @@
+//! Persistent conversation history, keyed by the platform-native id of each bot reply.
+//!
+//! The `message` handler used to reconstruct context by splitting the replied-to message on
+//! `\n` and inferring roles from `✅`/`☑️`/`💬` prefixes, which broke on anything that didn't
+//! survive Discord's formatting and couldn't recover content that was truncated or edited away.
+//! Sled lets a reply look up its parent's exact message list directly, and keeps it across
+//! restarts.
+//!
+//! Keys are plain strings rather than Discord's `u64` snowflakes so the same store works for any
+//! [`crate::frontend::ChatFrontend`] implementation, including Matrix's opaque event ids.
+
+use async_openai::types::ChatCompletionRequestMessage;
+
+/// A sled-backed map from a bot reply's platform-native message id to the full ordered
+/// conversation that produced it.
+#[derive(Clone)]
+pub struct HistoryStore {
+    db: sled::Db,
+}
+
+impl HistoryStore {
+    /// Open (or create) the history database at `path`.
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Look up the conversation thread that led to `message_id`, if one was recorded.
+    pub fn get(
+        &self,
+        message_id: &str,
+    ) -> anyhow::Result<Option<Vec<ChatCompletionRequestMessage>>> {
+        let Some(bytes) = self.db.get(message_id)? else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    /// Record `thread` as the conversation that led to `message_id`.
+    pub fn put(
+        &self,
+        message_id: &str,
+        thread: &[ChatCompletionRequestMessage],
+    ) -> anyhow::Result<()> {
+        self.db.insert(message_id, serde_json::to_vec(thread)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! Pluggable chat-completion backends.
+//!
+//! `run_chat_completition` used to be welded directly to `async_openai::Client` and the
+//! `gpt-4-0613` model string. [`ChatBackend`] pulls that call, and the provider-specific
+//! function/tool schema translation, behind one seam so a cheaper or self-hosted model can
+//! be swapped in without touching the function-calling engine in `chatbot`.
+
+use async_openai::types::{
+    ChatCompletionFunctions, ChatCompletionRequestMessage, CreateChatCompletionRequestArgs, Role,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+
+/// A single requested function call, already parsed out of whatever shape the provider used.
+#[derive(Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: serde_json::Value,
+}
+
+/// The outcome of one completion round.
+#[derive(Debug, Clone)]
+pub enum CompletionResult {
+    /// The model wants to call one or more functions, to be dispatched in parallel.
+    FunctionCalls(Vec<FunctionCall>),
+    /// The model produced a final text answer.
+    Text(String),
+}
+
+/// One incremental fragment of a streamed completion.
+#[derive(Debug, Clone)]
+pub enum StreamDelta {
+    /// A fragment of the final text answer.
+    Text(String),
+    /// A fragment of a function call's arguments as they're streamed in; `name` is set once,
+    /// on the fragment that first identifies which function is being called.
+    FunctionCallFragment {
+        name: Option<String>,
+        arguments_fragment: String,
+    },
+}
+
+/// A chat-completion provider capable of function calling.
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    /// Run one completion round over `messages`, offering `functions` as callable tools.
+    async fn complete(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        functions: Vec<ChatCompletionFunctions>,
+    ) -> anyhow::Result<CompletionResult>;
+
+    /// Like [`Self::complete`], but invoke `on_delta` for every incremental fragment as it
+    /// arrives so callers can stream progress into the UI. The default implementation just
+    /// runs `complete` and reports the whole result as a single delta, for backends (like
+    /// Cohere's) with no streaming endpoint.
+    async fn complete_stream(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        functions: Vec<ChatCompletionFunctions>,
+        on_delta: &mut (dyn FnMut(StreamDelta) + Send),
+    ) -> anyhow::Result<CompletionResult> {
+        let result = self.complete(messages, functions).await?;
+        match &result {
+            CompletionResult::Text(text) => on_delta(StreamDelta::Text(text.clone())),
+            CompletionResult::FunctionCalls(calls) => {
+                for call in calls {
+                    on_delta(StreamDelta::FunctionCallFragment {
+                        name: Some(call.name.clone()),
+                        arguments_fragment: call.args.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Backend talking to the OpenAI chat completions API.
+pub struct OpenAiBackend {
+    client: async_openai::Client,
+    model: String,
+}
+
+impl OpenAiBackend {
+    pub fn new(client: async_openai::Client, model: impl Into<String>) -> Self {
+        Self {
+            client,
+            model: model.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for OpenAiBackend {
+    async fn complete(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        functions: Vec<ChatCompletionFunctions>,
+    ) -> anyhow::Result<CompletionResult> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .max_tokens(512u16)
+            .model(&self.model)
+            .messages(messages)
+            .functions(functions)
+            .function_call("auto")
+            .build()?;
+
+        let response_message = self
+            .client
+            .chat()
+            .create(request)
+            .await?
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("OpenAI returned no choices"))?
+            .message;
+
+        // Only the single `function_call` field is read; a `tool_calls` array from newer models
+        // is not parsed. The one call is wrapped in the same `FunctionCalls` shape the Cohere
+        // backend returns.
+        if let Some(function_call) = response_message.function_call {
+            let args: serde_json::Value = function_call.arguments.parse()?;
+            return Ok(CompletionResult::FunctionCalls(vec![FunctionCall {
+                name: function_call.name,
+                args,
+            }]));
+        }
+
+        Ok(CompletionResult::Text(
+            response_message.content.unwrap_or_default(),
+        ))
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        functions: Vec<ChatCompletionFunctions>,
+        on_delta: &mut (dyn FnMut(StreamDelta) + Send),
+    ) -> anyhow::Result<CompletionResult> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .max_tokens(512u16)
+            .model(&self.model)
+            .messages(messages)
+            .functions(functions)
+            .function_call("auto")
+            .build()?;
+
+        let mut stream = self.client.chat().create_stream(request).await?;
+
+        let mut text = String::new();
+        let mut function_name = String::new();
+        let mut function_arguments = String::new();
+
+        while let Some(chunk) = stream.next().await {
+            let Some(choice) = chunk?.choices.into_iter().next() else {
+                continue;
+            };
+            let delta = choice.delta;
+
+            if let Some(fragment) = delta.content {
+                text.push_str(&fragment);
+                on_delta(StreamDelta::Text(fragment));
+            }
+            if let Some(function_call) = delta.function_call {
+                if let Some(name) = &function_call.name {
+                    function_name = name.clone();
+                }
+                let arguments_fragment = function_call.arguments.unwrap_or_default();
+                function_arguments.push_str(&arguments_fragment);
+                if function_call.name.is_some() || !arguments_fragment.is_empty() {
+                    on_delta(StreamDelta::FunctionCallFragment {
+                        name: function_call.name,
+                        arguments_fragment,
+                    });
+                }
+            }
+        }
+
+        if !function_name.is_empty() {
+            let args: serde_json::Value = function_arguments.parse()?;
+            return Ok(CompletionResult::FunctionCalls(vec![FunctionCall {
+                name: function_name,
+                args,
+            }]));
+        }
+
+        Ok(CompletionResult::Text(text))
+    }
+}
+
+/// Backend talking to Cohere's `/v1/chat` endpoint.
+///
+/// Cohere has no concept of OpenAI-style "functions"; instead it takes a `tools` array with
+/// `parameter_definitions`, and returns requested calls as `tool_calls` rather than a single
+/// `function_call`. We translate both directions here so the rest of the bot never has to know.
+pub struct CohereBackend {
+    api_key: String,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl CohereBackend {
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Convert our OpenAI-shaped function schema into Cohere's `tools` shape.
+    fn functions_to_tools(functions: &[ChatCompletionFunctions]) -> serde_json::Value {
+        json!(functions
+            .iter()
+            .map(|func| {
+                let properties = func
+                    .parameters
+                    .get("properties")
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                json!({
+                    "name": func.name,
+                    "description": func.description,
+                    "parameter_definitions": properties,
+                })
+            })
+            .collect::<Vec<_>>())
+    }
+
+    /// Split `messages` into Cohere's `chat_history` plus the trailing current `message`.
+    fn split_history(
+        messages: Vec<ChatCompletionRequestMessage>,
+    ) -> (Vec<serde_json::Value>, String) {
+        let mut history = Vec::new();
+        let mut current_message = String::new();
+        let len = messages.len();
+        for (index, message) in messages.into_iter().enumerate() {
+            if index == len - 1 && message.role == Role::User {
+                current_message = message.content;
+                continue;
+            }
+            let role = match message.role {
+                Role::User => "USER",
+                Role::Assistant => "CHATBOT",
+                Role::System | Role::Function => "SYSTEM",
+            };
+            history.push(json!({ "role": role, "message": message.content }));
+        }
+        (history, current_message)
+    }
+}
+
+#[async_trait]
+impl ChatBackend for CohereBackend {
+    async fn complete(
+        &self,
+        messages: Vec<ChatCompletionRequestMessage>,
+        functions: Vec<ChatCompletionFunctions>,
+    ) -> anyhow::Result<CompletionResult> {
+        let (chat_history, message) = Self::split_history(messages);
+
+        let response = self
+            .client
+            .post("https://api.cohere.ai/v1/chat")
+            .bearer_auth(&self.api_key)
+            .json(&json!({
+                "model": self.model,
+                "message": message,
+                "chat_history": chat_history,
+                "tools": Self::functions_to_tools(&functions),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+
+        if let Some(tool_calls) = response["tool_calls"].as_array() {
+            if !tool_calls.is_empty() {
+                let calls = tool_calls
+                    .iter()
+                    .map(|tool_call| {
+                        let name = tool_call["name"]
+                            .as_str()
+                            .ok_or_else(|| anyhow::anyhow!("Cohere tool call missing name"))?
+                            .to_string();
+                        let args = tool_call["parameters"].clone();
+                        Ok(FunctionCall { name, args })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                return Ok(CompletionResult::FunctionCalls(calls));
+            }
+        }
+
+        let text = response["text"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Cohere response missing text"))?
+            .to_string();
+        Ok(CompletionResult::Text(text))
+    }
+}
@@ -1,39 +1,360 @@
 use std::env;
+use std::sync::Arc;
+
+mod apis;
+mod cache;
+mod chatbot;
+mod commands;
+mod examples;
+mod frontend;
+mod history;
+mod llm;
+mod plugins;
+mod provider;
+mod telemetry;
 
 use serenity::{
     async_trait,
-    model::{channel::Message as DiscordMessage, gateway::Ready},
+    model::{
+        application::{command::Command, interaction::Interaction},
+        channel::Message as DiscordMessage,
+        gateway::Ready,
+    },
     prelude::{
         Client as DiscordClient, Context as DiscordContext, EventHandler, GatewayIntents,
         TypeMapKey,
     },
 };
 
-use async_openai::{
-    types::{
-        ChatCompletionRequestMessage, ChatCompletionRequestMessageArgs,
-        CreateChatCompletionRequestArgs, CreateChatCompletionResponse, Role,
-    },
-    Client as OpenAiClient,
-};
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestMessageArgs, Role};
+use futures::future::join_all;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
-struct OpenAiApi;
-impl TypeMapKey for OpenAiApi {
-    type Value = OpenAiClient;
+use frontend::{ChatFrontend, DiscordFrontend, IncomingMessage};
+use history::HistoryStore;
+use llm::{CompletionResult, StreamDelta};
+use provider::LlmProvider;
+
+struct HistoryDb;
+impl TypeMapKey for HistoryDb {
+    type Value = HistoryStore;
 }
 
 use rand::seq::SliceRandom;
 use regex::Regex;
 
+/// How often to flush accumulated stream deltas into the in-progress reply, to stay under the
+/// frontend's edit rate limit while still feeling live.
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(1200);
+/// Discord's hard cap on a single message's content length; reused as the chunk size for every
+/// frontend, since it's the tightest limit any of them impose.
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+/// Maximum number of function calls in a single turn, counted across all rounds, mirroring the
+/// budget the function-calling engine used to enforce on itself before it was merged in here.
+const MAX_FUNCTION_ROUNDS: u32 = 10;
+
+/// Excitable little status lines shown while the bot works through its 3-step pipeline.
+const REPLY_MESSAGES: &[&str] = &[
+    "Hey there! Super excited to process your message, give me just a moment... 🎬",
+    "Oh, a message! Can't wait to dive into this one - I'm on it... 🎥",
+    "Hey, awesome! A new message to explore! Let me work my media magic... 📺",
+    "Woo-hoo! A fresh message to check out! Let me put my CineMatic touch on it... 🍿",
+    "Yay, another message! Time to unleash my media passion, be right back... 📼",
+    "Hey, a message! I'm so excited to process this one, just a moment... 🎞",
+    "Aha! A message has arrived! Let me roll out the red carpet for it... 🎞️",
+    "Ooh, a new message to dissect! Allow me to unleash my inner film buff... 🎦",
+    "Lights, camera, action! Time to process your message with a cinematic twist... 📽️",
+    "Hooray, a message to dig into! Let's make this a blockbuster experience... 🌟",
+    "Greetings! Your message has caught my eye, let me give it the star treatment... 🎟️",
+    "Popcorn's ready! Let me take a closer look at your message like a true film fanatic... 🍿",
+    "Woohoo! A message to analyze! Let me work on it while humming my favorite movie tunes... 🎶",
+    "A new message to dive into! Let me put on my director's hat and get to work... 🎩",
+    "And... action! Time to process your message with my media expertise... 📹",
+    "Sending your message to the cutting room! Let me work on it like a skilled film editor... 🎞️",
+    "A message has entered the scene! Let me put my media prowess to work on it... 🎭",
+    "Your message is the star of the show! Let me process it with the passion of a true cinephile... 🌟",
+    "Curtain up! Your message takes center stage, and I'm ready to give it a standing ovation... 🎦",
+];
+
 struct Handler;
 
+/// Split `text` into chunks that fit under `limit` characters, preferring to break on line
+/// boundaries. A fenced code block (```) that would otherwise be split across chunks is closed
+/// at the end of one chunk and re-opened at the start of the next. A single line longer than
+/// `limit` on its own is hard-wrapped, since there's no boundary left to prefer.
+fn split_for_discord(text: &str, limit: usize) -> Vec<String> {
+    // Leave room for the "\n```" that closes a fence split across chunks
+    let limit = limit.saturating_sub(4);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut in_fence = false;
+    for line in text.split('\n') {
+        let mut candidate = current.clone();
+        if !candidate.is_empty() {
+            candidate.push('\n');
+        }
+        candidate.push_str(line);
+        if candidate.chars().count() > limit && !current.is_empty() {
+            if in_fence {
+                current.push_str("\n```");
+            }
+            chunks.push(std::mem::take(&mut current));
+            if in_fence {
+                current.push_str("```");
+            }
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        while current.chars().count() > limit {
+            let head: String = current.chars().take(limit).collect();
+            current = current.chars().skip(limit).collect();
+            chunks.push(head);
+        }
+    }
+    chunks.push(current);
+    chunks
+}
+
+/// Edit the in-progress reply (and any overflow continuations) on `frontend` to show `prefix`
+/// followed by `body`, split into chunks via `split_for_discord`. `overflow_ids` grows by one
+/// continuation message for each chunk beyond the messages it already holds; every
+/// continuation chunk gets `continuation_prefix` so it still reads as the bot's reply when
+/// viewed (or replied to) on its own.
+async fn flush_response(
+    frontend: &dyn ChatFrontend,
+    bot_message_id: &str,
+    overflow_ids: &mut Vec<String>,
+    prefix: &str,
+    continuation_prefix: &str,
+    body: &str,
+) {
+    let full = format!("{prefix}{body}");
+    // Continuation chunks get `continuation_prefix` after splitting, so leave room for it
+    let mut chunks = split_for_discord(
+        &full,
+        DISCORD_MESSAGE_LIMIT - continuation_prefix.chars().count(),
+    );
+    let first = chunks.remove(0);
+    if let Err(error) = frontend.edit(bot_message_id, &first).await {
+        tracing::error!(%error, "Error editing reply");
+    }
+
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let chunk = format!("{continuation_prefix}{chunk}");
+        if let Some(existing) = overflow_ids.get(index) {
+            if let Err(error) = frontend.edit(existing, &chunk).await {
+                tracing::error!(%error, "Error editing overflow message");
+            }
+        } else {
+            match frontend.send(&chunk).await {
+                Ok(sent_id) => overflow_ids.push(sent_id),
+                Err(error) => tracing::error!(%error, "Error sending overflow message"),
+            }
+        }
+    }
+}
+
+/// Spawn a best-effort flush of the in-progress answer into the reply. Used from the
+/// synchronous `on_delta` callback `ChatBackend::complete_stream` drives, where blocking on a
+/// frontend round-trip would stall the completion itself; `overflow_ids` is shared behind a
+/// mutex since a round's final flush can race with the next round's mid-stream ones.
+fn spawn_flush(
+    frontend: Arc<dyn ChatFrontend>,
+    overflow_ids: Arc<Mutex<Vec<String>>>,
+    bot_message_id: String,
+    prefix: String,
+    body: String,
+) {
+    tokio::spawn(async move {
+        let mut overflow_ids = overflow_ids.lock().await;
+        flush_response(
+            frontend.as_ref(),
+            &bot_message_id,
+            &mut overflow_ids,
+            &prefix,
+            "",
+            &body,
+        )
+        .await;
+    });
+}
+
+/// Drive the function-calling engine (`chatbot::get_functions`/`run_function`) to completion,
+/// streaming progress into the in-progress reply as it goes: text deltas as the model produces
+/// them, and a status line for each round of function calls while they're dispatched. Once the
+/// model settles on a final answer (or `MAX_FUNCTION_ROUNDS` is reached), records the full
+/// thread in `history_db` keyed by every message in the reply chain, so the user can reply to
+/// any of them on their next turn.
+async fn run_chat_completion(
+    frontend: Arc<dyn ChatFrontend>,
+    history_db: HistoryStore,
+    user_name: String,
+    bot_message_id: String,
+    message_history_text: String,
+    mut current_message: Vec<ChatCompletionRequestMessage>,
+) {
+    let backend = apis::get_chat_backend();
+    let overflow_ids: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut extra_history_text = String::new();
+    let mut final_response = String::new();
+
+    let mut round = 0;
+    while round < MAX_FUNCTION_ROUNDS {
+        let mut streamed_text = String::new();
+        let mut last_flush = Instant::now();
+        let completion =
+            chatbot::complete_with_retry(backend.as_ref(), &current_message, &mut |delta| {
+                if let StreamDelta::Text(fragment) = delta {
+                    streamed_text.push_str(&fragment);
+                    if last_flush.elapsed() >= STREAM_FLUSH_INTERVAL {
+                        spawn_flush(
+                            frontend.clone(),
+                            overflow_ids.clone(),
+                            bot_message_id.clone(),
+                            message_history_text.clone(),
+                            format!("{extra_history_text}{streamed_text}"),
+                        );
+                        last_flush = Instant::now();
+                    }
+                }
+            })
+            .await;
+
+        let completion = match completion {
+            Ok(completion) => completion,
+            Err(error) => {
+                tracing::error!(%error, "Error running chat completion");
+                let mut overflow_ids = overflow_ids.lock().await;
+                flush_response(
+                    frontend.as_ref(),
+                    &bot_message_id,
+                    &mut overflow_ids,
+                    &message_history_text,
+                    "",
+                    &format!("{extra_history_text}⚠️ Something went wrong: {error}"),
+                )
+                .await;
+                return;
+            }
+        };
+
+        let mut calls = match completion {
+            CompletionResult::Text(text) => {
+                final_response = text;
+                break;
+            }
+            CompletionResult::FunctionCalls(calls) => calls,
+        };
+        // Keep a single round's calls from blowing through the whole budget at once
+        calls.truncate((MAX_FUNCTION_ROUNDS - round) as usize);
+
+        // Show every call in this round running at once
+        let running_text = calls
+            .iter()
+            .map(|call| {
+                format!(
+                    "⌛ Running function {} with arguments {}",
+                    call.name, call.args
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        spawn_flush(
+            frontend.clone(),
+            overflow_ids.clone(),
+            bot_message_id.clone(),
+            message_history_text.clone(),
+            format!("{extra_history_text}{running_text}"),
+        );
+
+        // Dispatch every call in the round concurrently rather than one at a time
+        let responses = join_all(calls.into_iter().map(|call| {
+            let user_name = user_name.clone();
+            async move {
+                let response =
+                    chatbot::run_function(call.name.clone(), call.args, &user_name).await;
+                (call.name, response)
+            }
+        }))
+        .await;
+
+        for (function_name, function_response) in responses {
+            let function_response_text =
+                function_response.unwrap_or_else(|error| error.to_string());
+            let function_response_short: String =
+                function_response_text.chars().take(150).collect();
+            extra_history_text.push_str(&format!(
+                "🎬 Ran function {function_name} {function_response_short}\n"
+            ));
+
+            match ChatCompletionRequestMessageArgs::default()
+                .role(Role::Function)
+                .name(function_name)
+                .content(function_response_text)
+                .build()
+            {
+                Ok(message) => current_message.push(message),
+                Err(error) => tracing::error!(%error, "Error building function result message"),
+            }
+            round += 1;
+        }
+
+        // Show the results of every call in the round before starting the next one
+        let mut overflow_ids = overflow_ids.lock().await;
+        flush_response(
+            frontend.as_ref(),
+            &bot_message_id,
+            &mut overflow_ids,
+            &message_history_text,
+            "",
+            &extra_history_text,
+        )
+        .await;
+    }
+
+    // Final flush, marking the reply complete
+    let mut overflow_ids = overflow_ids.lock().await;
+    flush_response(
+        frontend.as_ref(),
+        &bot_message_id,
+        &mut overflow_ids,
+        &message_history_text,
+        "✅ ",
+        &format!("{extra_history_text}✅ {final_response}"),
+    )
+    .await;
+
+    // Record the thread that produced this reply, keyed by every message in the chain (the
+    // reply plus any overflow continuations), so a reply to any of them resumes the same thread
+    // instead of only working against the last one
+    current_message.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content(final_response)
+            .build()
+            .unwrap(),
+    );
+    for message_id in std::iter::once(&bot_message_id).chain(overflow_ids.iter()) {
+        if let Err(error) = history_db.put(message_id, &current_message) {
+            tracing::error!(%error, "Error saving conversation history");
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(user_id = %user_id, user_name = %user_name))]
 async fn process_chat(
-    openai_client: &OpenAiClient,
+    history_db: HistoryStore,
     user_name: String,                                  // The users name
     user_id: String,                                    // The users id
     user_text: String,                                  // Users text to bot
-    ctx: DiscordContext,                                // The discord context
-    mut bot_message: DiscordMessage,                    // The reply to the user
+    frontend: Arc<dyn ChatFrontend>,                    // The frontend the reply arrived on
+    bot_message_id: String,                             // The reply to the user
     message_history: Vec<ChatCompletionRequestMessage>, // The message history
     message_history_text: String,                       // The message history text
     reply_text: String, // The text used in the reply while processing
@@ -41,7 +362,7 @@ async fn process_chat(
     // Don't reply to non media queries, compare user_text with the ai model
     let mut user_text_total = String::new();
     // Get messages from user, add their text plus a new line
-    for message in message_history {
+    for message in &message_history {
         if message.role == Role::User {
             user_text_total.push_str(&format!("{}\n", &message.content));
         }
@@ -54,108 +375,225 @@ async fn process_chat(
         .replace("💬", "")
         .trim()
         .to_string();
-    let request = CreateChatCompletionRequestArgs::default()
-        .max_tokens(4u16)
-        .model("gpt-3.5-turbo")
-        .n(3u8)
-        .messages([
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::System)
-                .content("You determine if a users message is irrelevant to you, is it related to movies, series, asking for recommendations, changing resolution, adding or removing media, checking disk space, viewing users memories etc? You reply with a single word answer, yes or no.")
-                .build().unwrap(),
-            ChatCompletionRequestMessageArgs::default()
-                .role(Role::User)
-                .content(format!("{user_text_total}\nDo not respond to the above message, is the above text irrelevant? Reply with a single word answer, only say yes if certain"))
-                .build().unwrap(),
-        ])
-        .build().unwrap();
+    let provider = crate::provider::HttpLlmProvider::from_credentials().with_model("gpt-3.5-turbo");
+    let messages = vec![
+        crate::provider::ChatMessage {
+            role: "system",
+            content: "You determine if a users message is irrelevant to you, is it related to movies, series, asking for recommendations, changing resolution, adding or removing media, checking disk space, viewing users memories etc? You reply with a single word answer, yes or no.".to_string(),
+        },
+        crate::provider::ChatMessage {
+            role: "user",
+            content: format!("{user_text_total}\nDo not respond to the above message, is the above text irrelevant? Reply with a single word answer, only say yes if certain"),
+        },
+    ];
+    let opts = crate::provider::CompletionOpts {
+        max_tokens: 4,
+        n: 3,
+    };
 
     let mut tries = 0;
     let response = loop {
-        let response = openai_client.chat().create(request.clone()).await;
-        if let Ok(response) = response {
-            break Ok(response);
-        } else {
-            tries += 1;
-            if tries >= 3 {
-                break response;
-            }
+        let response = provider.complete(messages.clone(), opts.clone()).await;
+        if response.is_ok() {
+            break response;
+        }
+        tries += 1;
+        if tries >= 3 {
+            break response;
         }
     };
 
-    // TODO log the openai call and response
+    tracing::debug!(tries, "relevance vote llm provider call");
 
     // Return from errors
-    if let Err(error) = response {
-        println!("Error: {:?}", error);
-        return;
-    }
-    let response: CreateChatCompletionResponse = response.unwrap();
-
-    // Check each response choice for a yes
-    let mut is_valid = false;
-    for choice in response.choices {
-        if !choice.message.content.to_lowercase().contains("yes") {
-            is_valid = true;
+    let choices = match response {
+        Ok(choices) => choices,
+        Err(error) => {
+            tracing::error!(%error, "relevance vote llm provider call failed");
+            return;
         }
-    }
+    };
+
+    // Each choice votes "yes" (irrelevant) or not; only treat the message as irrelevant if a
+    // majority of the votes agree, so one dissenting vote can't overrule the rest either way
+    let irrelevant_votes = choices
+        .iter()
+        .filter(|choice| choice.to_lowercase().contains("yes"))
+        .count();
+    let is_valid = irrelevant_votes * 2 <= choices.len();
     if !is_valid {
         // Edit the message to let the user know the message is not valid
-        bot_message
-            .edit(&ctx.http, |msg: &mut serenity::builder::EditMessage| {
-                msg.content(format!("{message_history_text}❌ Hi, I'm a media bot. I can help you with media related questions. What would you like to know or achieve?"))
-            })
+        if let Err(error) = frontend
+            .edit(&bot_message_id, &format!("{message_history_text}❌ Hi, I'm a media bot. I can help you with media related questions. What would you like to know or achieve?"))
             .await
-            .unwrap();
+        {
+            tracing::error!(%error, "Error editing reply");
+        }
         return;
     }
 
     // Edit the bot_message to let the user know the message is valid and it is progressing
-    bot_message
-        .edit(&ctx.http, |msg| {
-            msg.content(format!("{message_history_text}⌛ 2/3 {reply_text}"))
-        })
+    if let Err(error) = frontend
+        .edit(
+            &bot_message_id,
+            &format!("{message_history_text}⌛ 2/3 {reply_text}"),
+        )
         .await
-        .unwrap();
+    {
+        tracing::error!(%error, "Error editing reply");
+    }
 
-    // TODO Get relevant examples
-    // relevantExamples = Examples.get_examples(userTextHistory + userText)
+    // Get relevant few-shot examples for the user's message
+    let relevant_examples =
+        examples::get_examples(&format!("{user_text_total}\n{user_text}")).await;
 
     // Edit the bot_message to let the user know it is progressing
-    bot_message
-        .edit(&ctx.http, |msg| {
-            msg.content(format!("{message_history_text}⌛ 3/3 {reply_text}"))
-        })
+    if let Err(error) = frontend
+        .edit(
+            &bot_message_id,
+            &format!("{message_history_text}⌛ 3/3 {reply_text}"),
+        )
+        .await
+    {
+        tracing::error!(%error, "Error editing reply");
+    }
+
+    // Build the messages to send: a friendly greeting, the reconstructed history, the relevant
+    // few-shot examples, then the user's new message
+    let mut current_message = vec![
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(format!("Hi my name is {user_name}"))
+            .build()
+            .unwrap(),
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::Assistant)
+            .content("Hi, how can I help you?")
+            .build()
+            .unwrap(),
+    ];
+    current_message.extend(message_history);
+    current_message.extend(relevant_examples);
+    current_message.push(
+        ChatCompletionRequestMessageArgs::default()
+            .role(Role::User)
+            .content(user_text)
+            .build()
+            .unwrap(),
+    );
+
+    run_chat_completion(
+        frontend,
+        history_db,
+        user_name,
+        bot_message_id,
+        message_history_text,
+        current_message,
+    )
+    .await;
+}
+
+/// Shared entry point for an incoming message on any [`ChatFrontend`]: looks up reply history,
+/// posts the initial "working on it" reply, and spawns `process_chat` to do the rest. Each
+/// frontend's own event handling does the platform-specific work of deciding whether a message
+/// is addressed to the bot at all (Discord's mention/`!` check, say) before building an
+/// `IncomingMessage` and calling this.
+async fn handle_incoming(
+    history_db: HistoryStore,
+    frontend: Arc<dyn ChatFrontend>,
+    incoming: IncomingMessage,
+) {
+    let user_text = incoming.content.trim().to_string();
+    if user_text.is_empty() {
+        return;
+    }
+
+    // If message is a reply to the bot, look up the thread that produced it directly rather
+    // than scraping it back out of the reply's rendered text
+    let mut message_history: Vec<ChatCompletionRequestMessage> = Vec::new();
+    if let Some(reply_to) = &incoming.reply_to {
+        match history_db.get(reply_to) {
+            Ok(Some(thread)) => message_history = thread,
+            Ok(None) => return,
+            Err(error) => {
+                tracing::error!(%error, "Error reading conversation history");
+                return;
+            }
+        }
+    }
+
+    tracing::info!(user_id = %incoming.author_id, user_name = %incoming.author_name, content = %user_text, "message received");
+
+    let mut message_history_text = String::new();
+    for message in &message_history {
+        let prefix = match message.role {
+            Role::Assistant => "☑️ ",
+            Role::User => "💬 ",
+            Role::System | Role::Function => "",
+        };
+        message_history_text.push_str(&format!("{prefix}{}\n", message.content));
+    }
+    // Add the users message to the message history
+    message_history_text.push_str(&format!("💬 {user_text}\n"));
+
+    // Choose a random reply message
+    let reply_text = REPLY_MESSAGES
+        .choose(&mut rand::thread_rng())
+        .expect("Failed to choose reply message")
+        .to_string();
+    // Send a reply message to the user
+    let bot_message_id = match frontend
+        .reply(&format!("{message_history_text}⌛ 1/3 {reply_text}"))
         .await
-        .unwrap();
-
-    // # Get current messages
-    // currentMessage = []
-    // currentMessage.append({"role": "user", "content": f"Hi my name is {usersName}"})
-    // currentMessage.append({"role": "assistant", "content": f"Hi, how can I help you?"})
-    // # Add message history
-    // for message in messageHistory:
-    //     currentMessage.append(message)
-    // # Add users message
-    // currentMessage.append({"role": "user", "content": userText})
-
-    // # Run chat completion
-    // await runChatCompletion(
-    //     botsMessage,
-    //     botsStartMessage,
-    //     usersName,
-    //     usersId,
-    //     currentMessage,
-    //     relevantExamples,
-    //     0,
-    // )
+    {
+        Ok(id) => id,
+        Err(error) => {
+            tracing::error!(%error, "Error sending initial reply");
+            return;
+        }
+    };
+
+    let user_name = incoming.author_name;
+    let user_id = incoming.author_id;
+    // Spawn a new thread to process the message
+    tokio::spawn(async move {
+        process_chat(
+            history_db,
+            user_name,
+            user_id,
+            user_text,
+            frontend,
+            bot_message_id,
+            message_history,
+            message_history_text,
+            reply_text,
+        )
+        .await;
+    });
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     // When the bot is ready
-    async fn ready(&self, _: DiscordContext, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+    async fn ready(&self, ctx: DiscordContext, ready: Ready) {
+        tracing::info!(user = %ready.user.name, "connected to discord");
+
+        // Register get_functions() as native slash commands so users who know exactly what
+        // they want get instant, deterministic execution without a GPT round-trip
+        if let Err(error) =
+            Command::set_global_application_commands(&ctx.http, commands::build_commands).await
+        {
+            tracing::error!(%error, "Error registering slash commands");
+        }
+    }
+
+    // When a slash command is invoked
+    async fn interaction_create(&self, ctx: DiscordContext, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = interaction {
+            if let Err(error) = commands::handle_command(&ctx, &command).await {
+                tracing::error!(command = %command.data.name, %error, "Error handling command");
+            }
+        }
     }
 
     // When message is received
@@ -185,17 +623,16 @@ impl EventHandler for Handler {
                     }
                 }
                 Err(error) => {
-                    println!("Error checking mentions: {:?}", error);
+                    tracing::error!(%error, "Error checking mentions");
                     return;
                 }
             }
         }
 
-        // If message is a reply to the bot, create a message history
-        let mut message_history: Vec<ChatCompletionRequestMessage> = Vec::new();
-        let mut valid_reply = false;
+        // If this message replies to the bot, pass along the replied-to message's id so
+        // `handle_incoming` can look up the thread it belongs to
+        let mut reply_to = None;
         if let Some(message_reference) = &msg.message_reference {
-            // Get the message replied to
             let replied_to = match msg
                 .channel_id
                 .message(&ctx.http, message_reference.message_id.unwrap())
@@ -203,145 +640,64 @@ impl EventHandler for Handler {
             {
                 Ok(replied_to) => replied_to,
                 Err(error) => {
-                    println!("Error getting replied to message: {:?}", error);
+                    tracing::error!(%error, "Error getting replied to message");
                     return;
                 }
             };
-            if replied_to.author.id == bot_user.id {
-                // See if the message is completed
-                if !replied_to.content.contains("✅") {
-                    return;
-                }
-                valid_reply = true;
-                // Split message by lines
-                let content = replied_to.content.split("\n");
-                for msg in content {
-                    // If the line is a reply to the bot, add it to the message history
-                    if msg.starts_with("✅") {
-                        message_history.push(
-                            ChatCompletionRequestMessageArgs::default()
-                                .role(Role::Assistant)
-                                .content(msg.replace("✅ ", "☑️ ").trim())
-                                .build()
-                                .unwrap(),
-                        );
-                    } else if msg.starts_with("☑️") {
-                        message_history.push(
-                            ChatCompletionRequestMessageArgs::default()
-                                .role(Role::Assistant)
-                                .content(msg.trim())
-                                .build()
-                                .unwrap(),
-                        );
-                    // If the line is a reply to the user, add it to the message history
-                    } else if msg.starts_with("💬") {
-                        message_history.push(
-                            ChatCompletionRequestMessageArgs::default()
-                                .role(Role::User)
-                                .content(msg.trim())
-                                .build()
-                                .unwrap(),
-                        );
-                    }
-                }
+            if replied_to.author.id != bot_user.id {
+                return;
             }
-        } else {
-            valid_reply = true;
-        }
-        // If reply was not valid end
-        if !valid_reply {
-            return;
+            reply_to = Some(replied_to.id.0.to_string());
         }
 
-        // Collect users id and name
-        let user_id = msg.author.id.to_string();
-        let user_name = msg.author.name.clone();
-        println!("Message from {} ({}): {}", user_name, user_id, msg.content);
-
         // Remove new lines, mentions and trim whitespace
         let regex = Regex::new(r"(?m)<[@#]&?\d+>").unwrap();
-        let mut user_text = msg.content.replace("\n", " ").to_string();
-        user_text = regex.replace_all(&user_text, "").trim().to_string();
+        let mut content = msg.content.replace("\n", " ").to_string();
+        content = regex.replace_all(&content, "").trim().to_string();
         if cfg!(debug_assertions) {
             // Remove the first char "!" in debug
-            user_text = user_text[1..].trim().to_string();
-        }
-
-        if user_text == "" {
-            return;
+            content = content[1..].trim().to_string();
         }
 
-        let mut message_history_text = String::new();
-        for msg in &message_history {
-            message_history_text.push_str(&format!("{}\n", msg.content));
-        }
-        // Add the users message to the message history
-        message_history_text.push_str(&format!("💬 {user_text}\n"));
-
-        let reply_messages = vec![
-            "Hey there! Super excited to process your message, give me just a moment... 🎬",
-            "Oh, a message! Can't wait to dive into this one - I'm on it... 🎥",
-            "Hey, awesome! A new message to explore! Let me work my media magic... 📺",
-            "Woo-hoo! A fresh message to check out! Let me put my CineMatic touch on it... 🍿",
-            "Yay, another message! Time to unleash my media passion, be right back... 📼",
-            "Hey, a message! I'm so excited to process this one, just a moment... 🎞",
-            "Aha! A message has arrived! Let me roll out the red carpet for it... 🎞️",
-            "Ooh, a new message to dissect! Allow me to unleash my inner film buff... 🎦",
-            "Lights, camera, action! Time to process your message with a cinematic twist... 📽️",
-            "Hooray, a message to dig into! Let's make this a blockbuster experience... 🌟",
-            "Greetings! Your message has caught my eye, let me give it the star treatment... 🎟️",
-            "Popcorn's ready! Let me take a closer look at your message like a true film fanatic... 🍿",
-            "Woohoo! A message to analyze! Let me work on it while humming my favorite movie tunes... 🎶",
-            "A new message to dive into! Let me put on my director's hat and get to work... 🎩",
-            "And... action! Time to process your message with my media expertise... 📹",
-            "Sending your message to the cutting room! Let me work on it like a skilled film editor... 🎞️",
-            "A message has entered the scene! Let me put my media prowess to work on it... 🎭",
-            "Your message is the star of the show! Let me process it with the passion of a true cinephile... 🌟",
-            "Curtain up! Your message takes center stage, and I'm ready to give it a standing ovation... 🎦",
-        ];
-        // Choose a random reply message
-        let reply_text = reply_messages
-            .choose(&mut rand::thread_rng())
-            .expect("Failed to choose reply message")
-            .to_string();
-        // Send a reply message to the user
-        let bot_message = msg
-            .reply(
-                &ctx.http,
-                format!("{message_history_text}⌛ 1/3 {reply_text}"),
-            )
-            .await
-            .expect("Failed to send message");
+        let incoming = IncomingMessage {
+            author_id: msg.author.id.to_string(),
+            author_name: msg.author.name.clone(),
+            content,
+            reply_to,
+        };
+        let frontend: Arc<dyn ChatFrontend> = Arc::new(DiscordFrontend::new(ctx.clone(), msg));
 
-        // Get the openai client from the context
+        // Get the history db from the context
         let data = (&ctx.data).read().await;
-        let openai_client = data.get::<OpenAiApi>().unwrap().clone();
-        let ctx_clone = (&ctx).clone();
-
-        // Spawn a new thread to process the message
-        tokio::spawn(async move {
-            process_chat(
-                &openai_client,
-                user_name,
-                user_id,
-                user_text,
-                ctx_clone,
-                bot_message,
-                message_history,
-                message_history_text,
-                reply_text,
-            )
-            .await;
-        });
+        let history_db = data.get::<HistoryDb>().unwrap().clone();
+        drop(data);
+
+        handle_incoming(history_db, frontend, incoming).await;
     }
 }
 
 #[tokio::main]
 async fn main() {
-    // Configure the client with your openai token in the environment.
-    let openai_api_key: String =
-        env::var("OPENAI_API_KEY").expect("Expected a openai token in the environment");
-    let openai_client = OpenAiClient::new().with_api_key(openai_api_key);
+    telemetry::init();
+
+    // Open the conversation history database
+    let history_db = HistoryStore::open("history.sled").expect("Failed to open history database");
+
+    // The `[frontend]` section in credentials.toml picks which chat platform to run against,
+    // defaulting to Discord so existing setups keep working unchanged.
+    let frontend_kind = apis::get_credentials()
+        .get("frontend")
+        .and_then(|frontend| frontend.get("kind"))
+        .and_then(toml::Value::as_str)
+        .unwrap_or("discord")
+        .to_string();
+
+    if frontend_kind == "matrix" {
+        if let Err(error) = frontend::run_matrix(history_db).await {
+            tracing::error!(%error, "matrix client error");
+        }
+        return;
+    }
 
     // Configure the client with your Discord bot token in the environment.
     let discord_token: String =
@@ -354,13 +710,13 @@ async fn main() {
     // Create a new instance of the Client, logging in as a bot
     let mut client: DiscordClient = DiscordClient::builder(&discord_token, intents)
         .event_handler(Handler)
-        .type_map_insert::<OpenAiApi>(openai_client)
+        .type_map_insert::<HistoryDb>(history_db)
         .await
         .expect("Err creating client");
 
     // Finally, start a single shard, and start listening to events.
     // Shards will automatically attempt to reconnect, and will perform exponential backoff until it reconnects.
     if let Err(why) = client.start().await {
-        println!("Client error: {:?}", why);
+        tracing::error!(error = %why, "client error");
     }
 }
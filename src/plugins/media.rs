@@ -0,0 +1,327 @@
+//! Sonarr/Radarr-backed implementations of the `media_*` tools.
+//!
+//! Each public `*_args` function matches `chatbot`'s `FuncType` signature so it can be wired
+//! straight into a `Func`; the actual logic lives in the `async fn` it wraps.
+
+use crate::apis::{arr_request, gpt_info_query, ArrService, HttpMethod};
+use anyhow::anyhow;
+use futures::Future;
+use std::{collections::HashMap, pin::Pin};
+
+fn arr_service_for(format: &str) -> anyhow::Result<ArrService> {
+    match format {
+        "movie" => Ok(ArrService::Radarr),
+        "series" => Ok(ArrService::Sonarr),
+        other => Err(anyhow!("Unknown format {other}, expected movie or series")),
+    }
+}
+
+fn arr_path(service: &ArrService, suffix: &str) -> String {
+    match service {
+        ArrService::Sonarr => format!("/api/v3/series{suffix}"),
+        ArrService::Radarr => format!("/api/v3/movie{suffix}"),
+    }
+}
+
+fn required<'a>(args: &'a HashMap<String, String>, key: &str) -> anyhow::Result<&'a str> {
+    args.get(key)
+        .map(String::as_str)
+        .ok_or_else(|| anyhow!("Missing required argument {key}"))
+}
+
+/// Percent-encode `term` into a `term=...` query string segment, so search terms containing
+/// reserved characters (`&`, `#`, `?`, non-ASCII titles, etc.) don't corrupt the request.
+fn term_query(term: &str) -> String {
+    reqwest::Url::parse_with_params("http://localhost/", &[("term", term)])
+        .expect("Failed to build lookup query")
+        .query()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Look up the quality profile whose name best matches `quality` (e.g. "1080p"), falling back
+/// to the server's first configured profile if nothing matches exactly.
+async fn quality_profile_id(service: ArrService, quality: &str) -> anyhow::Result<i64> {
+    let profiles = arr_request(
+        HttpMethod::Get,
+        service,
+        "/api/v3/qualityprofile".to_string(),
+        None,
+    )
+    .await;
+    let profiles = profiles
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected an array of quality profiles"))?;
+
+    let matched = profiles.iter().find(|profile| {
+        profile["name"]
+            .as_str()
+            .is_some_and(|name| name.eq_ignore_ascii_case(quality))
+    });
+
+    matched
+        .or_else(|| profiles.first())
+        .ok_or_else(|| anyhow!("No quality profiles configured"))?["id"]
+        .as_i64()
+        .ok_or_else(|| anyhow!("Quality profile missing id"))
+}
+
+pub(crate) fn lookup_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(lookup(args))
+}
+
+async fn lookup(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let format = required(&args, "format")?;
+    let service = arr_service_for(format)?;
+    let searches = required(&args, "searches")?;
+    let query = required(&args, "query")?;
+
+    let mut data = String::new();
+    for term in searches
+        .split('|')
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+    {
+        let results = arr_request(
+            HttpMethod::Get,
+            service.clone(),
+            format!("{}?{}", arr_path(&service, "/lookup"), term_query(term)),
+            None,
+        )
+        .await;
+        data.push_str(&format!("Results for \"{term}\":\n{results}\n\n"));
+    }
+
+    gpt_info_query("gpt-3.5-turbo".to_string(), data, query.to_string())
+        .await
+        .map_err(|error| anyhow!(error))
+}
+
+pub(crate) fn add_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(add(args))
+}
+
+async fn add(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let format = required(&args, "format")?;
+    let service = arr_service_for(format)?;
+    let db_id = required(&args, "db_id")?;
+    let quality = args.get("quality").map_or("1080p", String::as_str);
+
+    let quality_profile_id = quality_profile_id(service.clone(), quality).await?;
+    let root_folder = root_folder_path(service.clone()).await?;
+
+    let lookup_field = match service {
+        ArrService::Sonarr => "tvdbId",
+        ArrService::Radarr => "tmdbId",
+    };
+    let results = arr_request(
+        HttpMethod::Get,
+        service.clone(),
+        format!(
+            "{}?term={lookup_field}:{db_id}",
+            arr_path(&service, "/lookup")
+        ),
+        None,
+    )
+    .await;
+    let mut item = results
+        .as_array()
+        .and_then(|results| results.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("No {format} found for id {db_id}"))?;
+
+    let item_map = item
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Unexpected lookup response shape"))?;
+    item_map.insert(
+        "qualityProfileId".to_string(),
+        serde_json::json!(quality_profile_id),
+    );
+    item_map.insert("rootFolderPath".to_string(), serde_json::json!(root_folder));
+    item_map.insert("monitored".to_string(), serde_json::json!(true));
+    item_map.insert(
+        "addOptions".to_string(),
+        serde_json::json!({ "searchForMovie": true, "searchForMissingEpisodes": true }),
+    );
+
+    arr_request(
+        HttpMethod::Post,
+        service.clone(),
+        arr_path(&service, ""),
+        Some(item.to_string()),
+    )
+    .await;
+
+    let title = item["title"].as_str().unwrap_or(db_id);
+    Ok(format!("Added {title} to {format}s at {quality} quality"))
+}
+
+/// The server's first configured root folder, used as the destination for newly added media.
+async fn root_folder_path(service: ArrService) -> anyhow::Result<String> {
+    let root_folders = arr_request(
+        HttpMethod::Get,
+        service,
+        "/api/v3/rootfolder".to_string(),
+        None,
+    )
+    .await;
+    root_folders
+        .as_array()
+        .and_then(|root_folders| root_folders.first())
+        .and_then(|root_folder| root_folder["path"].as_str())
+        .map(ToString::to_string)
+        .ok_or_else(|| anyhow!("No root folders configured"))
+}
+
+pub(crate) fn setres_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(setres(args))
+}
+
+async fn setres(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let format = required(&args, "format")?;
+    let service = arr_service_for(format)?;
+    let id = required(&args, "id")?;
+    let quality = required(&args, "quality")?;
+
+    let quality_profile_id = quality_profile_id(service.clone(), quality).await?;
+
+    let mut item = arr_request(
+        HttpMethod::Get,
+        service.clone(),
+        format!("{}/{id}", arr_path(&service, "")),
+        None,
+    )
+    .await;
+    let item_map = item
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Unexpected {format} response shape"))?;
+    item_map.insert(
+        "qualityProfileId".to_string(),
+        serde_json::json!(quality_profile_id),
+    );
+
+    arr_request(
+        HttpMethod::Put,
+        service,
+        format!("{}/{id}", arr_path(&service, "")),
+        Some(item.to_string()),
+    )
+    .await;
+
+    Ok(format!("Set {format} {id} to {quality} quality"))
+}
+
+pub(crate) fn remove_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(remove(args))
+}
+
+async fn remove(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let format = required(&args, "format")?;
+    let service = arr_service_for(format)?;
+    let id = required(&args, "id")?;
+
+    arr_request(
+        HttpMethod::Delete,
+        service.clone(),
+        format!("{}/{id}", arr_path(&service, "")),
+        None,
+    )
+    .await;
+
+    Ok(format!("Removed {format} {id} from the server"))
+}
+
+pub(crate) fn wanted_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(wanted(args))
+}
+
+async fn wanted(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let format = required(&args, "format")?;
+    let service = arr_service_for(format)?;
+    let user = args.get("user").map_or("none", String::as_str);
+
+    let wanted = arr_request(
+        HttpMethod::Get,
+        service.clone(),
+        "/api/v3/wanted/missing?pageSize=200".to_string(),
+        None,
+    )
+    .await;
+    let records = wanted["records"].as_array().cloned().unwrap_or_default();
+
+    let titles: Vec<String> = if user == "self" {
+        let user_name = args
+            .get("user_name")
+            .map_or("", String::as_str)
+            .to_lowercase();
+        let tag_name = format!("added-{user_name}");
+        let all_tags = arr_request(HttpMethod::Get, service, "/api/v3/tag".to_string(), None).await;
+        let tag_id = all_tags
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|tag| tag["label"].as_str() == Some(tag_name.as_str()))
+            .and_then(|tag| tag["id"].as_i64());
+
+        records
+            .iter()
+            .filter(|record| {
+                tag_id.is_some_and(|tag_id| {
+                    record["tags"]
+                        .as_array()
+                        .is_some_and(|tags| tags.iter().any(|tag| tag.as_i64() == Some(tag_id)))
+                })
+            })
+            .map(|record| record["title"].as_str().unwrap_or("Unknown").to_string())
+            .collect()
+    } else {
+        records
+            .iter()
+            .filter(|record| record["tags"].as_array().map_or(true, Vec::is_empty))
+            .map(|record| record["title"].as_str().unwrap_or("Unknown").to_string())
+            .collect()
+    };
+
+    if titles.is_empty() {
+        return Ok(format!("No wanted {format}s found"));
+    }
+    Ok(format!("Wanted {format}s: {}", titles.join(", ")))
+}
+
+pub(crate) fn downloads_args(
+    _args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(downloads())
+}
+
+async fn downloads() -> anyhow::Result<String> {
+    let mut lines = Vec::new();
+    for service in [ArrService::Sonarr, ArrService::Radarr] {
+        let label = service.to_string();
+        let queue = arr_request(HttpMethod::Get, service, "/api/v3/queue".to_string(), None).await;
+        let records = queue["records"].as_array().cloned().unwrap_or_default();
+        if records.is_empty() {
+            lines.push(format!("{label}: nothing downloading"));
+            continue;
+        }
+        for record in records {
+            let title = record["title"].as_str().unwrap_or("Unknown");
+            let status = record["status"].as_str().unwrap_or("unknown");
+            let time_left = record["timeleft"].as_str().unwrap_or("unknown");
+            lines.push(format!(
+                "{label}: {title} - {status}, {time_left} remaining"
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,7 @@
+//! Concrete implementations of the tools `chatbot::get_functions()` advertises to the
+//! function-calling engine (and, via `commands.rs`, to native Discord slash commands).
+//! Keeping them in their own module rather than inline in `chatbot` keeps the dispatch table in
+//! `chatbot` focused on the function-calling plumbing.
+
+pub(crate) mod media;
+pub(crate) mod websearch;
@@ -0,0 +1,88 @@
+//! Web search backing the `web_search` tool, for questions that fall outside what Sonarr/Radarr
+//! know about. Queries the Brave Search API (configured via the `[websearch]` section of
+//! credentials.toml) and summarises the top results with the same `gpt_info_query` helper the
+//! `media_lookup` tool uses to comb through JSON.
+
+use crate::apis::gpt_info_query;
+use anyhow::anyhow;
+use futures::Future;
+use std::{collections::HashMap, pin::Pin};
+
+pub(crate) fn ai_search_args(
+    args: HashMap<String, String>,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> {
+    Box::pin(ai_search(args))
+}
+
+async fn ai_search(args: HashMap<String, String>) -> anyhow::Result<String> {
+    let query = args
+        .get("query")
+        .ok_or_else(|| anyhow!("Missing required argument query"))?;
+
+    let results = brave_search(query).await?;
+    if results.is_empty() {
+        return Ok("No web results found".to_string());
+    }
+
+    let data = results
+        .iter()
+        .map(|result| format!("{}\n{}\n{}", result.title, result.url, result.description))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    gpt_info_query(
+        "gpt-3.5-turbo".to_string(),
+        data,
+        format!("Answer the following question using only the search results above: {query}"),
+    )
+    .await
+    .map_err(|error| anyhow!(error))
+}
+
+struct SearchResult {
+    title: String,
+    url: String,
+    description: String,
+}
+
+/// Query the Brave Search API's `/res/v1/web/search` endpoint for `query`, returning up to 5
+/// results.
+async fn brave_search(query: &str) -> anyhow::Result<Vec<SearchResult>> {
+    let cred = crate::apis::get_credentials();
+    let api_key = cred
+        .get("websearch")
+        .and_then(|section| section.get("api_key"))
+        .and_then(toml::Value::as_str)
+        .ok_or_else(|| {
+            anyhow!("Expected an api_key in the [websearch] section of credentials.toml")
+        })?;
+
+    let response = reqwest::Client::new()
+        .get("https://api.search.brave.com/res/v1/web/search")
+        .query(&[("q", query)])
+        .header("X-Subscription-Token", api_key)
+        .header("Accept", "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<serde_json::Value>()
+        .await?;
+
+    let results = response["web"]["results"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(results
+        .into_iter()
+        .take(5)
+        .map(|result| SearchResult {
+            title: result["title"].as_str().unwrap_or_default().to_string(),
+            url: result["url"].as_str().unwrap_or_default().to_string(),
+            description: result["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect())
+}
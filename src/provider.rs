@@ -0,0 +1,196 @@
+//! Pluggable LLM provider for the plain (non-function-calling) completions used by
+//! `gpt_info_query` and the relevance check in `process_chat`.
+//!
+//! `get_openai()` used to be the only way either of those talked to a model. Config lives
+//! under `[provider]` in credentials.toml so they can point at Azure OpenAI or a self-hosted
+//! OpenAI-compatible endpoint (Ollama, LocalAI) through config. The `model` key there is only a
+//! default: `gpt_info_query` and the relevance check both set their own model with
+//! [`HttpLlmProvider::with_model`], so neither of them uses the configured model.
+
+use async_trait::async_trait;
+use serde_json::json;
+
+/// One message in a conversation, independent of any particular provider's request shape.
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: &'static str,
+    pub content: String,
+}
+
+/// Options for a single completion request.
+#[derive(Debug, Clone)]
+pub struct CompletionOpts {
+    pub max_tokens: u16,
+    /// Number of independent completions to sample, e.g. the relevance check's n=3 vote.
+    pub n: u8,
+}
+
+impl Default for CompletionOpts {
+    fn default() -> Self {
+        Self {
+            max_tokens: 512,
+            n: 1,
+        }
+    }
+}
+
+/// A provider of simple chat completions (no function calling).
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    async fn complete(
+        &self,
+        messages: Vec<ChatMessage>,
+        opts: CompletionOpts,
+    ) -> anyhow::Result<Vec<String>>;
+}
+
+/// Which wire format to speak. OpenAI, Azure OpenAI and OpenAI-compatible self-hosted servers
+/// all return the same `choices[].message.content` shape; only the URL and auth header differ.
+enum ProviderKind {
+    OpenAi,
+    AzureOpenAi { api_version: String },
+    OpenAiCompatible,
+}
+
+/// A provider talking to OpenAI, Azure OpenAI, or an OpenAI-compatible endpoint, selected by
+/// the `[provider]` section of credentials.toml.
+pub struct HttpLlmProvider {
+    kind: ProviderKind,
+    api_base: String,
+    api_key: String,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl HttpLlmProvider {
+    /// Build the provider selected by credentials.toml, defaulting to OpenAI's public API with
+    /// `gpt-3.5-turbo` when `[provider]` is unset so existing setups keep working unchanged.
+    pub fn from_credentials() -> Self {
+        let cred = crate::apis::get_credentials();
+        let provider = cred.get("provider").and_then(toml::Value::as_table);
+
+        let provider_type = provider
+            .and_then(|provider| provider.get("type"))
+            .and_then(toml::Value::as_str)
+            .unwrap_or("openai");
+        let model = provider
+            .and_then(|provider| provider.get("model"))
+            .and_then(toml::Value::as_str)
+            .unwrap_or("gpt-3.5-turbo")
+            .to_string();
+        let api_key = provider
+            .and_then(|provider| provider.get("api_key"))
+            .and_then(toml::Value::as_str)
+            .map_or_else(|| crate::apis::get_openai_api_key(), str::to_string);
+
+        match provider_type {
+            "azure-openai" => {
+                let api_base = provider
+                    .and_then(|provider| provider.get("api_base"))
+                    .and_then(toml::Value::as_str)
+                    .expect("Expected an api_base for the azure-openai provider")
+                    .to_string();
+                let api_version = provider
+                    .and_then(|provider| provider.get("api_version"))
+                    .and_then(toml::Value::as_str)
+                    .unwrap_or("2023-07-01-preview")
+                    .to_string();
+                Self {
+                    kind: ProviderKind::AzureOpenAi { api_version },
+                    api_base,
+                    api_key,
+                    model,
+                    client: reqwest::Client::new(),
+                }
+            }
+            "openai-compatible" => {
+                let api_base = provider
+                    .and_then(|provider| provider.get("api_base"))
+                    .and_then(toml::Value::as_str)
+                    .expect("Expected an api_base for the openai-compatible provider")
+                    .to_string();
+                Self {
+                    kind: ProviderKind::OpenAiCompatible,
+                    api_base,
+                    api_key,
+                    model,
+                    client: reqwest::Client::new(),
+                }
+            }
+            _ => Self {
+                kind: ProviderKind::OpenAi,
+                api_base: "https://api.openai.com".to_string(),
+                api_key,
+                model,
+                client: reqwest::Client::new(),
+            },
+        }
+    }
+
+    /// Override the configured model, for callers (like `gpt_info_query`) that pick a model
+    /// per call rather than relying on credentials.toml.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    fn request_url(&self) -> String {
+        match &self.kind {
+            ProviderKind::AzureOpenAi { api_version } => format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={api_version}",
+                self.api_base, self.model
+            ),
+            ProviderKind::OpenAi | ProviderKind::OpenAiCompatible => {
+                format!("{}/v1/chat/completions", self.api_base)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for HttpLlmProvider {
+    async fn complete(
+        &self,
+        messages: Vec<ChatMessage>,
+        opts: CompletionOpts,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut body = json!({
+            "messages": messages
+                .iter()
+                .map(|message| json!({ "role": message.role, "content": message.content }))
+                .collect::<Vec<_>>(),
+            "max_tokens": opts.max_tokens,
+            "n": opts.n,
+        });
+        if !matches!(self.kind, ProviderKind::AzureOpenAi { .. }) {
+            body["model"] = json!(self.model);
+        }
+
+        let request = self.client.post(self.request_url()).json(&body);
+        let request = match self.kind {
+            ProviderKind::AzureOpenAi { .. } => request.header("api-key", &self.api_key),
+            ProviderKind::OpenAi | ProviderKind::OpenAiCompatible => {
+                request.bearer_auth(&self.api_key)
+            }
+        };
+
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+
+        response["choices"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Provider response missing choices"))?
+            .iter()
+            .map(|choice| {
+                choice["message"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow::anyhow!("Provider choice missing message content"))
+            })
+            .collect()
+    }
+}
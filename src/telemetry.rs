@@ -0,0 +1,52 @@
+//! Tracing setup: an env-filtered console subscriber, plus an optional OpenTelemetry OTLP
+//! exporter so operators can ship traces of every LLM call and arr request to a collector
+//! instead of having to read them back out of `println!` output.
+//!
+//! The OTLP exporter is enabled by adding an `[telemetry]` section with an `otlp_endpoint` to
+//! credentials.toml; with no section present, only the console subscriber runs.
+
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Initialise the global tracing subscriber. Must be called once, at startup.
+pub fn init() {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(tracing_subscriber::fmt::layer());
+
+    let Some(otlp_endpoint) = otlp_endpoint() else {
+        registry.init();
+        return;
+    };
+
+    let tracer = match opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&otlp_endpoint),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+    {
+        Ok(tracer) => tracer,
+        Err(error) => {
+            eprintln!("Failed to install OTLP exporter, falling back to console only: {error:?}");
+            registry.init();
+            return;
+        }
+    };
+
+    registry
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .init();
+}
+
+/// The `[telemetry].otlp_endpoint` from credentials.toml, if set.
+fn otlp_endpoint() -> Option<String> {
+    crate::apis::get_credentials()
+        .get("telemetry")?
+        .as_table()?
+        .get("otlp_endpoint")?
+        .as_str()
+        .map(str::to_string)
+}